@@ -0,0 +1,495 @@
+use core::fmt::Debug;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame, Rgb, RgbImage};
+use num::{clamp, Float, ToPrimitive};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use thiserror::Error;
+use wav::BitDepth;
+
+type Color = Rgb<u8>;
+
+/// Anything that can go wrong while turning an audio file into an image.
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("could not open input file: {0}")]
+    Open(std::io::Error),
+    #[error("could not read wav data: {0}")]
+    Read(std::io::Error),
+    #[error("unsupported or empty bit depth")]
+    UnsupportedBitDepth,
+    #[error("input file contains no audio frames")]
+    EmptyInput,
+    #[error("unsupported output format for '{0}'")]
+    UnsupportedOutputFormat(String),
+    #[error("invalid color string '{0}', expected '#rrggbb'")]
+    InvalidColor(String),
+    #[error("invalid {option} value '{value}', expected {expected}")]
+    InvalidOption {
+        option: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+    #[error("could not encode output image: {0}")]
+    Encode(image::ImageError),
+    #[error("could not encode PNG: {0}")]
+    Png(png::EncodingError),
+}
+
+/// Properties of the source audio, embedded into PNG metadata so the generated
+/// thumbnail is self-describing.
+pub struct Metadata {
+    pub sample_rate: u32,
+    pub channel_count: u16,
+    pub bit_depth: u16,
+    pub frame_count: usize,
+    pub duration_seconds: f64,
+}
+
+/// How the channels of a multichannel file are arranged in the output image.
+pub enum Layout {
+    /// Each channel gets its own horizontal lane, stacked top to bottom.
+    Lanes,
+    /// Every channel is drawn over the full height, sharing one center line.
+    Overlay,
+}
+
+/// How a column's amplitude is summarised from its sample window.
+pub enum Mode {
+    /// Minimum and maximum sample in the window (the original behaviour).
+    Peak,
+    /// Root-mean-square of the window, drawn symmetrically about the center.
+    Rms,
+}
+
+/// How an amplitude is mapped onto the vertical axis.
+pub enum Scale {
+    /// Amplitude scales linearly from silence to full scale.
+    Linear,
+    /// Amplitude is expressed in decibels, clamped to `floor` dB.
+    Db { floor: f64 },
+}
+
+/// Everything `render_waveform` needs to draw a single image.
+pub struct Config {
+    pub input_file: String,
+    pub width: usize,
+    pub height: usize,
+    pub fg_color: Color,
+    pub bg_color: Color,
+    pub layout: Layout,
+    pub mode: Mode,
+    pub scale: Scale,
+}
+
+fn scale_to_range<T: Float>(value: T, in_begin: T, in_end: T, out_begin: T, out_end: T) -> T {
+    out_begin + (value - in_begin) / (in_end - in_begin) * (out_end - out_begin)
+}
+
+fn clamp_scale<T: Float + Debug>(value: T, in_begin: T, in_end: T, out_begin: T, out_end: T) -> T {
+    clamp(
+        scale_to_range(value, in_begin, in_end, out_begin, out_end),
+        out_begin,
+        out_end,
+    )
+}
+
+fn saturating_cast(value: usize) -> u32 {
+    value.try_into().unwrap_or(u32::MAX)
+}
+
+fn upscale_image(image: &RgbImage, new_width: u32) -> RgbImage {
+    let mut output_image = RgbImage::new(new_width, image.height());
+    for row in 0..output_image.height() {
+        for column in 0..output_image.width() {
+            let pi = column * image.width() / output_image.width();
+            output_image.put_pixel(column, row, *image.get_pixel(pi, row));
+        }
+    }
+
+    output_image
+}
+
+struct Wave<'a, T> {
+    data: &'a Vec<T>,
+    channel_count: u16,
+}
+
+impl<'a, T> Wave<'a, T> {
+    fn frame_count(&self) -> usize {
+        assert!(self.data.len().is_multiple_of(self.channel_count as usize));
+        self.data.len() / self.channel_count as usize
+    }
+}
+
+// Map a non-negative amplitude (in sample units, measured from the silence
+// level) to a pixel offset in `0..=half` away from a lane's center line.
+fn amplitude_to_offset(amplitude: f64, full_scale: f64, half: f64, scale: &Scale) -> f64 {
+    match scale {
+        Scale::Linear => clamp_scale(amplitude, 0., full_scale, 0., half),
+        Scale::Db { floor } => {
+            if amplitude <= 0. {
+                return 0.;
+            }
+            let db = 20. * (amplitude / full_scale).log10();
+            clamp_scale(db, *floor, 0., 0., half)
+        }
+    }
+}
+
+// `zero_point` is the sample value that represents silence (0 for signed PCM
+// and IEEE float, 128 for unsigned 8-bit) and `full_scale` is the distance
+// from silence to a full-amplitude peak in the sample's own units. Together
+// they let a single generic routine scale signed, unsigned and float formats.
+fn draw_waveform<SampleType: PartialOrd + Into<f64> + Copy + Debug + Sync>(
+    config: &Config,
+    wave: &Wave<SampleType>,
+    zero_point: f64,
+    full_scale: f64,
+    progress: Option<&dyn Fn(f32)>,
+) -> RgbImage {
+    // The time axis is driven by frames, not interleaved samples, so the
+    // horizontal scale is independent of the channel count.
+    let frame_count = wave.frame_count();
+    let small_wave = frame_count < config.width;
+    let out_width = if small_wave { frame_count } else { config.width };
+    let mut image = RgbImage::from_pixel(
+        saturating_cast(out_width),
+        saturating_cast(config.height),
+        config.bg_color,
+    );
+
+    // A frameless wave yields a zero-width image; there is nothing to draw, and
+    // the samples-per-pixel division below would divide by zero. Hand back the
+    // bare background so callers still get a well-formed image.
+    if image.width() == 0 {
+        return image;
+    }
+
+    let channel_count = wave.channel_count as usize;
+    let frames_per_pixel = frame_count / image.width() as usize;
+    let lane_height = match config.layout {
+        Layout::Lanes => image.height() as usize / channel_count,
+        Layout::Overlay => image.height() as usize,
+    };
+
+    let total_columns = (channel_count * image.width() as usize) as f32;
+    let mut rendered_columns = 0.;
+
+    for channel in 0..channel_count {
+        let (lane_lo, lane_hi) = match config.layout {
+            Layout::Lanes => (channel * lane_height, (channel + 1) * lane_height),
+            Layout::Overlay => (0, image.height() as usize),
+        };
+        let lane_center = (lane_lo + lane_hi) as f64 / 2.;
+        let half = lane_center - lane_lo as f64;
+        let column_count = image.width() as usize;
+
+        // Computing a column's (bottom, top) span only reads the shared sample
+        // buffer, so the columns are independent. The actual `put_pixel` writes
+        // happen afterwards in a single pass that owns the buffer exclusively.
+        let span = |column: usize| -> (u32, u32) {
+            let fp = column * frames_per_pixel;
+
+            // Walk only this channel's samples by striding over the interleaved
+            // frames; float samples are not `Ord`, so fold by hand.
+            // A single pass collects both the peak extents and the sum of
+            // squared deviations needed for the RMS envelope.
+            let mut max = wave.data[fp * channel_count + channel];
+            let mut min = max;
+            let mut sum_squares = 0.;
+            let mut count = 0.;
+            for frame in fp..fp + frames_per_pixel {
+                let sample = wave.data[frame * channel_count + channel];
+                if sample > max {
+                    max = sample;
+                }
+                if sample < min {
+                    min = sample;
+                }
+                let deviation = sample.into() - zero_point;
+                sum_squares += deviation * deviation;
+                count += 1.;
+            }
+
+            // Amplitudes above and below the center line, in sample units.
+            let (top_amplitude, bottom_amplitude) = match config.mode {
+                Mode::Peak => (
+                    (max.into() - zero_point).max(0.),
+                    (zero_point - min.into()).max(0.),
+                ),
+                Mode::Rms => {
+                    let rms = (sum_squares / count).sqrt();
+                    (rms, rms)
+                }
+            };
+
+            let top_pixel = (lane_center
+                + amplitude_to_offset(top_amplitude, full_scale, half, &config.scale))
+            .round()
+            .to_u32()
+            .unwrap();
+            let bottom_pixel = (lane_center
+                - amplitude_to_offset(bottom_amplitude, full_scale, half, &config.scale))
+            .round()
+            .to_u32()
+            .unwrap();
+
+            (bottom_pixel, top_pixel)
+        };
+
+        #[cfg(feature = "parallel")]
+        let spans: Vec<(u32, u32)> = (0..column_count).into_par_iter().map(span).collect();
+        #[cfg(not(feature = "parallel"))]
+        let spans: Vec<(u32, u32)> = (0..column_count).map(span).collect();
+
+        for (column, (bottom_pixel, top_pixel)) in spans.into_iter().enumerate() {
+            for row in bottom_pixel..top_pixel {
+                image.put_pixel(saturating_cast(column), row, config.fg_color);
+            }
+
+            if let Some(report) = progress {
+                rendered_columns += 1.;
+                report(rendered_columns / total_columns);
+            }
+        }
+    }
+
+    if small_wave {
+        image = upscale_image(&image, config.width as u32);
+    }
+
+    image
+}
+
+/// Parse a `#rrggbb` color string into an [`Rgb`] pixel.
+pub fn parse_hex_color(hex_color: &str) -> Result<Color, Error> {
+    let invalid = || Error::InvalidColor(hex_color.to_string());
+    if hex_color.len() != 7 || !hex_color.starts_with('#') {
+        return Err(invalid());
+    }
+    let hex = &hex_color[1..]; // remove the "#" symbol
+    let r = u8::from_str_radix(&hex[0..2], 16).map_err(|_| invalid())?;
+    let g = u8::from_str_radix(&hex[2..4], 16).map_err(|_| invalid())?;
+    let b = u8::from_str_radix(&hex[4..6], 16).map_err(|_| invalid())?;
+    Ok(Rgb([r, g, b]))
+}
+
+/// Read the WAV file named by `config`, render it, and return the image.
+///
+/// The optional `progress` callback is invoked with a fraction in `0.0..=1.0`
+/// as columns are rendered, so an embedding GUI can show progress on long
+/// files.
+pub fn render_waveform(
+    config: &Config,
+    progress: Option<&dyn Fn(f32)>,
+) -> Result<(RgbImage, Metadata), Error> {
+    let mut inp_file = File::open(Path::new(&config.input_file)).map_err(Error::Open)?;
+    let (header, data) = wav::read(&mut inp_file).map_err(Error::Read)?;
+    let channel_count = header.channel_count;
+
+    let sample_count = match &data {
+        BitDepth::Eight(samples) => samples.len(),
+        BitDepth::Sixteen(samples) => samples.len(),
+        BitDepth::TwentyFour(samples) => samples.len(),
+        BitDepth::ThirtyTwoFloat(samples) => samples.len(),
+        BitDepth::Empty => return Err(Error::UnsupportedBitDepth),
+    };
+    let frame_count = sample_count / channel_count as usize;
+    if frame_count == 0 {
+        return Err(Error::EmptyInput);
+    }
+    let metadata = Metadata {
+        sample_rate: header.sampling_rate,
+        channel_count,
+        bit_depth: header.bits_per_sample,
+        frame_count,
+        duration_seconds: frame_count as f64 / header.sampling_rate as f64,
+    };
+
+    // Dispatch on the reader's actual `BitDepth`. Each representation carries a
+    // different silence level and full-scale peak (see `draw_waveform`): signed
+    // PCM is centered on zero, 8-bit PCM is unsigned and centered on 128, and
+    // IEEE float is normalized to +/-1.0.
+    let image = match &data {
+        BitDepth::Eight(_) => draw_waveform(
+            config,
+            &Wave {
+                data: data.as_eight().unwrap(),
+                channel_count,
+            },
+            128.,
+            127.,
+            progress,
+        ),
+        BitDepth::Sixteen(_) => draw_waveform(
+            config,
+            &Wave {
+                data: data.as_sixteen().unwrap(),
+                channel_count,
+            },
+            0.,
+            i16::MAX as f64,
+            progress,
+        ),
+        BitDepth::TwentyFour(_) => draw_waveform(
+            config,
+            &Wave {
+                data: data.as_twenty_four().unwrap(),
+                channel_count,
+            },
+            0.,
+            ((1i32 << 23) - 1) as f64,
+            progress,
+        ),
+        // `wav::BitDepth::ThirtyTwoFloat` carries IEEE floats (`Vec<f32>`)
+        // normalised to ±1.0, so the full-scale reference is 1.0 not `i32::MAX`.
+        BitDepth::ThirtyTwoFloat(_) => draw_waveform(
+            config,
+            &Wave {
+                data: data.as_thirty_two_float().unwrap(),
+                channel_count,
+            },
+            0.,
+            1.,
+            progress,
+        ),
+        BitDepth::Empty => unreachable!("empty bit depth handled above"),
+    };
+
+    Ok((image, metadata))
+}
+
+/// Save a rendered image, translating `image` crate failures into [`Error`].
+///
+/// PNG output additionally carries the source audio's [`Metadata`] in tEXt
+/// chunks; other formats are written as-is.
+pub fn save_image(
+    image: &RgbImage,
+    metadata: &Metadata,
+    out_file_path: &str,
+) -> Result<(), Error> {
+    let is_png = Path::new(out_file_path)
+        .extension()
+        .map(|extension| extension.eq_ignore_ascii_case("png"))
+        .unwrap_or(false);
+
+    if is_png {
+        return write_png(image, metadata, out_file_path);
+    }
+
+    image.save(out_file_path).map_err(|error| match error {
+        image::ImageError::Unsupported(_) => {
+            Error::UnsupportedOutputFormat(out_file_path.to_string())
+        }
+        other => Error::Encode(other),
+    })
+}
+
+// Write a PNG by hand so the source audio's properties can be attached as
+// tEXt chunks that PNG tooling (and `file`, browsers, etc.) can read back.
+fn write_png(image: &RgbImage, metadata: &Metadata, out_file_path: &str) -> Result<(), Error> {
+    let file = File::create(out_file_path)
+        .map_err(|error| Error::Encode(image::ImageError::IoError(error)))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    for (keyword, value) in [
+        ("SampleRate", metadata.sample_rate.to_string()),
+        ("Channels", metadata.channel_count.to_string()),
+        ("BitDepth", metadata.bit_depth.to_string()),
+        ("Frames", metadata.frame_count.to_string()),
+        ("Duration", format!("{:.3}", metadata.duration_seconds)),
+    ] {
+        encoder
+            .add_text_chunk(keyword.to_string(), value)
+            .map_err(Error::Png)?;
+    }
+
+    let mut writer = encoder.write_header().map_err(Error::Png)?;
+    writer.write_image_data(image.as_raw()).map_err(Error::Png)
+}
+
+/// Parameters for the animated-GIF output mode.
+pub struct Animation {
+    /// Number of frames the playhead sweep is divided into.
+    pub frames: u32,
+    /// Playback rate in frames per second.
+    pub fps: u32,
+    /// Color of the sweeping playhead line.
+    pub playhead_color: Color,
+    /// Width (in source pixels) of a zoomed, scrolling viewport. `None` keeps
+    /// the whole waveform visible and only moves the playhead across it.
+    pub window: Option<u32>,
+}
+
+// Paint a one-pixel-wide vertical playhead line down the whole image.
+fn draw_playhead(image: &mut RgbImage, column: u32, color: Color) {
+    let column = column.min(image.width().saturating_sub(1));
+    for row in 0..image.height() {
+        image.put_pixel(column, row, color);
+    }
+}
+
+// Compose the `index`-th animation frame from the static base waveform.
+fn compose_frame(base: &RgbImage, animation: &Animation, index: u32) -> RgbImage {
+    let width = base.width();
+    // `frames - 1` steps span the full travel, so the last frame lands exactly
+    // at the far edge; guard the single-frame case against division by zero.
+    let last_step = animation.frames.saturating_sub(1).max(1);
+
+    match animation.window {
+        None => {
+            let mut frame = base.clone();
+            let column = index * (width.saturating_sub(1)) / last_step;
+            draw_playhead(&mut frame, column, animation.playhead_color);
+            frame
+        }
+        Some(window) => {
+            let window = window.clamp(1, width);
+            let left = index * (width - window) / last_step;
+            let mut cropped = RgbImage::new(window, base.height());
+            for row in 0..base.height() {
+                for col in 0..window {
+                    cropped.put_pixel(col, row, *base.get_pixel(left + col, row));
+                }
+            }
+            // Stretch the viewport back to full width and keep the playhead
+            // pinned at its center while the waveform scrolls underneath.
+            let mut frame = upscale_image(&cropped, width);
+            draw_playhead(&mut frame, width / 2, animation.playhead_color);
+            frame
+        }
+    }
+}
+
+/// Render `base` into an animated GIF with a sweeping playhead.
+///
+/// The static waveform is drawn once (by the caller, via [`render_waveform`])
+/// and reused for every frame; only the playhead and optional scrolling
+/// viewport change between frames.
+pub fn save_animation(
+    base: &RgbImage,
+    animation: &Animation,
+    out_file_path: &str,
+) -> Result<(), Error> {
+    let file = File::create(out_file_path)
+        .map_err(|error| Error::Encode(image::ImageError::IoError(error)))?;
+    let mut encoder = GifEncoder::new(file);
+    encoder.set_repeat(Repeat::Infinite).map_err(Error::Encode)?;
+
+    let delay = Delay::from_numer_denom_ms(1000, animation.fps.max(1));
+    for index in 0..animation.frames.max(1) {
+        let frame = compose_frame(base, animation, index);
+        let rgba = DynamicImage::ImageRgb8(frame).to_rgba8();
+        encoder
+            .encode_frame(Frame::from_parts(rgba, 0, 0, delay))
+            .map_err(Error::Encode)?;
+    }
+
+    Ok(())
+}
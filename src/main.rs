@@ -1,155 +1,44 @@
-use argparse::{ArgumentParser, Store};
-use core::fmt::Debug;
-use image::{Rgb, RgbImage};
-use num::{clamp, Bounded, Float, ToPrimitive, Zero};
-use std::fs::File;
-use std::num::ParseIntError;
-use std::path::Path;
-use wav;
-
-fn scale_to_range<T: Float>(value: T, in_begin: T, in_end: T, out_begin: T, out_end: T) -> T {
-    out_begin + (value - in_begin) / (in_end - in_begin) * (out_end - out_begin)
-}
-
-fn clamp_scale<T: Float + Debug>(value: T, in_begin: T, in_end: T, out_begin: T, out_end: T) -> T {
-    clamp(
-        scale_to_range(value, in_begin, in_end, out_begin, out_end),
-        out_begin,
-        out_end,
-    )
-}
-
-fn saturating_cast(value: usize) -> u32 {
-    value.try_into().unwrap_or(u32::MAX)
-}
-
-fn upscale_image(image: &RgbImage, new_width: u32) -> RgbImage {
-    let mut output_image = RgbImage::new(new_width, image.height());
-    for row in 0..output_image.height() {
-        for column in 0..output_image.width() {
-            let pi = column * image.width() / output_image.width();
-            output_image.put_pixel(column, row, *image.get_pixel(pi, row));
-        }
+use argparse::{ArgumentParser, Store, StoreTrue};
+use std::io::Write;
+use wave_to_img::{
+    parse_hex_color, render_waveform, save_animation, save_image, Animation, Config, Error, Layout,
+    Mode, Scale,
+};
+
+fn parse_layout(layout: &str) -> Result<Layout, Error> {
+    match layout {
+        "lanes" => Ok(Layout::Lanes),
+        "overlay" => Ok(Layout::Overlay),
+        other => Err(Error::InvalidOption {
+            option: "layout",
+            value: other.to_string(),
+            expected: "'lanes' or 'overlay'",
+        }),
     }
-
-    output_image
-}
-
-struct Wave<'a, T> {
-    data: &'a Vec<T>,
-    channel_count: u16,
 }
 
-impl<'a, T> Wave<'a, T> {
-    fn frame_count(&self) -> usize {
-        assert!(self.data.len() % self.channel_count as usize == 0);
-        self.data.len() / self.channel_count as usize
-    }
-
-    fn sample_count(&self) -> usize {
-        self.data.len()
+fn parse_mode(mode: &str) -> Result<Mode, Error> {
+    match mode {
+        "peak" => Ok(Mode::Peak),
+        "rms" => Ok(Mode::Rms),
+        other => Err(Error::InvalidOption {
+            option: "mode",
+            value: other.to_string(),
+            expected: "'peak' or 'rms'",
+        }),
     }
 }
 
-type Color = Rgb<u8>;
-
-// TODO: This will not work for SampleType's that are unsigned
-fn draw_waveform<'a, SampleType: Ord + Zero + Into<f64> + Bounded + Copy + Debug>(
-    width: usize,
-    height: usize,
-    wave: &'a Wave<SampleType>,
-    fg_color: Color,
-    bg_color: Color,
-) -> RgbImage {
-    let small_wave = wave.frame_count() < width;
-    let mut image = if small_wave {
-        RgbImage::new(saturating_cast(wave.frame_count()), saturating_cast(height))
-    } else {
-        RgbImage::new(saturating_cast(width), saturating_cast(height))
-    };
-
-    let samples_per_pixel = wave.sample_count() / image.width() as usize;
-
-    for column in 0..image.width() as usize {
-        let sp = column * samples_per_pixel;
-
-        // TODO: maybe loop manually to make better use of cache
-        let max = *wave.data[sp..sp + samples_per_pixel].iter().max().unwrap();
-        let min = *wave.data[sp..sp + samples_per_pixel].iter().min().unwrap();
-
-        let top_pixel = clamp_scale(
-            max.into(),
-            0.,
-            SampleType::max_value().into(),
-            image.height().to_f64().unwrap() / 2.,
-            image.height().to_f64().unwrap(),
-        )
-        .round()
-        .to_u32()
-        .unwrap();
-
-        let bottom_pixel = clamp_scale(
-            min.into(),
-            SampleType::min_value().into(),
-            SampleType::zero().into(),
-            0.,
-            image.height().to_f64().unwrap() / 2.,
-        )
-        .round()
-        .to_u32()
-        .unwrap();
-
-        for row in 0..bottom_pixel {
-            image.put_pixel(saturating_cast(column), row, bg_color);
-        }
-        for row in bottom_pixel..top_pixel {
-            image.put_pixel(saturating_cast(column), row, fg_color);
-        }
-        for row in top_pixel..image.height() {
-            image.put_pixel(saturating_cast(column), row, bg_color);
-        }
+fn parse_scale(scale: &str, db_floor: f64) -> Result<Scale, Error> {
+    match scale {
+        "linear" => Ok(Scale::Linear),
+        "db" => Ok(Scale::Db { floor: db_floor }),
+        other => Err(Error::InvalidOption {
+            option: "scale",
+            value: other.to_string(),
+            expected: "'linear' or 'db'",
+        }),
     }
-
-    if small_wave {
-        image = upscale_image(&image, width as u32);
-    }
-
-    image
-}
-
-fn generate_png(
-    inp_file_path: &String,
-    out_file_path: &String,
-    height: usize,
-    width: usize,
-    fg_color: Color,
-    bg_color: Color,
-) {
-    let mut inp_file = File::open(Path::new(inp_file_path)).expect("could not open file");
-    let (header, data) = wav::read(&mut inp_file).expect("Coult not read wav file");
-    assert!(data.is_sixteen());
-    let image = draw_waveform(
-        width,
-        height,
-        &Wave::<i16> {
-            data: data.as_sixteen().unwrap(),
-            // data: &vec![i16::MAX/2, i16::MAX, i16::MIN, i16::MIN/2],
-            channel_count: header.channel_count,
-        },
-        fg_color,
-        bg_color,
-    );
-    image
-        .save(out_file_path)
-        .expect("Error while saving the image");
-}
-
-fn parse_hex_color(hex_color: &str) -> Result<Color, ParseIntError> {
-    let hex = &hex_color[1..]; // remove the "#" symbol
-    let r = u8::from_str_radix(&hex[0..2], 16)?;
-    let g = u8::from_str_radix(&hex[2..4], 16)?;
-    let b = u8::from_str_radix(&hex[4..6], 16)?;
-    Ok(Rgb([r, g, b]))
 }
 
 struct Options {
@@ -157,16 +46,66 @@ struct Options {
     height: usize,
     fg_color: String,
     bg_color: String,
+    layout: String,
+    mode: String,
+    scale: String,
+    db_floor: f64,
+    animate: bool,
+    frames: u32,
+    fps: u32,
+    playhead_color: String,
+    window: u32,
     input_file: String,
     output_file: String,
 }
 
+fn run(options: &Options) -> Result<(), Error> {
+    let config = Config {
+        input_file: options.input_file.clone(),
+        width: options.width,
+        height: options.height,
+        fg_color: parse_hex_color(&options.fg_color)?,
+        bg_color: parse_hex_color(&options.bg_color)?,
+        layout: parse_layout(&options.layout)?,
+        mode: parse_mode(&options.mode)?,
+        scale: parse_scale(&options.scale, options.db_floor)?,
+    };
+
+    let progress = |fraction: f32| {
+        print!("\rrendering... {:>3.0}%", fraction * 100.);
+        let _ = std::io::stdout().flush();
+    };
+    let (image, metadata) = render_waveform(&config, Some(&progress))?;
+    println!();
+
+    if options.animate {
+        let animation = Animation {
+            frames: options.frames,
+            fps: options.fps,
+            playhead_color: parse_hex_color(&options.playhead_color)?,
+            window: (options.window > 0).then_some(options.window),
+        };
+        save_animation(&image, &animation, &options.output_file)
+    } else {
+        save_image(&image, &metadata, &options.output_file)
+    }
+}
+
 fn main() {
     let mut options = Options {
         width: 1000,
         height: 250,
         fg_color: "#000000".to_string(),
         bg_color: "#ffffff".to_string(),
+        layout: "lanes".to_string(),
+        mode: "peak".to_string(),
+        scale: "linear".to_string(),
+        db_floor: -60.,
+        animate: false,
+        frames: 100,
+        fps: 30,
+        playhead_color: "#ff0000".to_string(),
+        window: 0,
         input_file: "".to_string(),
         output_file: "out.png".to_string(),
     };
@@ -189,6 +128,42 @@ fn main() {
             .add_option(&["--fg-color"], Store, "Foreground color");
         ap.refer(&mut options.bg_color)
             .add_option(&["--bg-color"], Store, "Background color");
+        ap.refer(&mut options.layout).add_option(
+            &["--layout"],
+            Store,
+            "Multichannel layout: 'lanes' or 'overlay'",
+        );
+        ap.refer(&mut options.mode).add_option(
+            &["--mode"],
+            Store,
+            "Amplitude summary: 'peak' or 'rms'",
+        );
+        ap.refer(&mut options.scale)
+            .add_option(&["--scale"], Store, "Amplitude scale: 'linear' or 'db'");
+        ap.refer(&mut options.db_floor).add_option(
+            &["--db-floor"],
+            Store,
+            "Lowest dB level drawn when --scale db (e.g. -60)",
+        );
+        ap.refer(&mut options.animate).add_option(
+            &["--animate"],
+            StoreTrue,
+            "Write an animated GIF with a sweeping playhead",
+        );
+        ap.refer(&mut options.frames)
+            .add_option(&["--frames"], Store, "Number of animation frames");
+        ap.refer(&mut options.fps)
+            .add_option(&["--fps"], Store, "Animation frame rate");
+        ap.refer(&mut options.playhead_color).add_option(
+            &["--playhead-color"],
+            Store,
+            "Playhead line color for --animate",
+        );
+        ap.refer(&mut options.window).add_option(
+            &["--window"],
+            Store,
+            "Zoomed scrolling viewport width in source pixels (0 = whole file)",
+        );
         ap.refer(&mut options.input_file)
             .add_option(&["-i", "--input"], Store, "Input file path");
         ap.refer(&mut options.output_file).add_option(
@@ -199,12 +174,8 @@ fn main() {
         ap.parse_args_or_exit();
     }
 
-    generate_png(
-        &options.input_file,
-        &options.output_file,
-        options.height,
-        options.width,
-        parse_hex_color(&options.fg_color).expect("Invalid fb color string"),
-        parse_hex_color(&options.bg_color).expect("Invalid bg color string"),
-    )
+    if let Err(error) = run(&options) {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
 }